@@ -1,110 +1,94 @@
-use std::error::Error;
-use std::ffi::CString;
-use std::os::raw::{c_char, c_int, c_void};
+mod os;
 
-// #[link(name = "libc")]
-extern "system" {
-    #[link_name = "dlopen"]
-    fn _dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
-    #[link_name = "dlclose"]
-    fn _dlclose(handle: *mut c_void) -> c_int;
-    #[link_name = "dlsym"]
-    fn _dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
-    #[link_name = "dlerror"]
-    fn _dlerror() -> *mut c_char;
-}
+#[cfg(unix)]
+pub use os::unix::*;
+
+#[cfg(windows)]
+pub use os::windows::*;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::os::raw::c_void;
 
-/// FileHandle serves the purpose of not having direct access to the raw pointer
-/// handle returned by dlopen outside of this library.
-/// Every FileHandle corresponds to exactly one dlopen/dlclose calling pair.
-/// As such, there is no implementation of the Copy-Trait
-pub struct FileHandle {
-    _private: *mut c_void,
+/// A higher-level wrapper around a `FileHandle` that caches resolved symbols
+/// so repeated lookups of the same name only pay the `dlsym` cost once, and
+/// keeps track of the path it was opened with.
+pub struct Library {
+    handle: FileHandle,
+    path: String,
+    table: HashMap<String, *mut c_void>,
 }
 
-impl FileHandle {
-    /// Creates a handle that does not represent a file opened with dlopen.
-    /// Use case: replacing a FileHandle field in a struct.
-    pub fn invalid() -> Self {
-        FileHandle {
-            _private: std::ptr::null_mut(),
-        }
+impl Library {
+    /// Opens `path` with `dlopen` and wraps the resulting handle.
+    pub fn open(path: &str, flags: i32) -> Result<Self, Box<dyn Error>> {
+        let handle = dlopen(path, flags)?;
+        Ok(Library {
+            handle,
+            path: path.to_owned(),
+            table: HashMap::new(),
+        })
     }
 
-    pub fn is_valid(&self) -> bool {
-        !self._private.is_null()
+    /// Returns the path this library was originally opened with.
+    pub fn name(&self) -> &str {
+        &self.path
     }
-}
 
-// flags that can be passed to dlopen
-pub const RTLD_LAZY: i32 = 0x00001;
-pub const RTLD_NOW: i32 = 0x00002;
-pub const RTLD_BINDING_MASK: i32 = 0x00003;
-pub const RTLD_NOLOAD: i32 = 0x00004;
-pub const RTLD_DEEPBIND: i32 = 0x00008;
-pub const RTLD_GLOBAL: i32 = 0x00100;
-pub const RTLD_LOCAL: i32 = 0;
-pub const RTLD_NODELETE: i32 = 0x01000;
-
-pub fn dlopen(filename: &str, flags: i32) -> Result<FileHandle, Box<dyn Error>> {
-    if filename == "" {
-        let handle = unsafe {
-            FileHandle {
-                _private: _dlopen(std::ptr::null(), flags),
-            }
-        };
-        return Ok(handle);
-    } else {
-        let cstr_filename;
-        match CString::new(filename) {
-            Err(e) => return Err(Box::new(e)),
-            Ok(value) => {
-                cstr_filename = value;
-            }
+    /// Resolves `name`, returning the cached pointer on repeat lookups and
+    /// falling back to `dlsym` (populating the cache) on a miss.
+    pub fn sym(&mut self, name: &str) -> Result<*mut c_void, Box<dyn Error>> {
+        if let Some(pointer) = self.table.get(name) {
+            return Ok(*pointer);
         }
 
-        let handle = unsafe {
-            FileHandle {
-                _private: _dlopen(cstr_filename.as_ptr(), flags),
-            }
-        };
-        return Ok(handle);
+        let pointer = dlsym(&self.handle, name)?;
+        self.table.insert(name.to_owned(), pointer);
+        Ok(pointer)
     }
-}
 
-pub fn dlclose(handle: FileHandle) -> i32 {
-    unsafe { _dlclose(handle._private) }
+    /// Gives access to the raw symbol cache for inspection. Unsafe because
+    /// the returned pointers are only valid for as long as this `Library`
+    /// keeps its `FileHandle` open.
+    ///
+    /// # Safety
+    ///
+    /// The pointers in the returned map must not be dereferenced or called
+    /// after this `Library` is dropped (which closes the underlying
+    /// `FileHandle`), and are only valid for the library they were resolved
+    /// from in the first place.
+    pub unsafe fn table(&self) -> &HashMap<String, *mut c_void> {
+        &self.table
+    }
 }
 
-pub fn dlsym(handle: &FileHandle, symbol: &str) -> Result<*mut c_void, Box<dyn Error>> {
-    let cstr_symbol;
-    match CString::new(symbol) {
-        Err(e) => {
-            return Err(Box::new(e));
-        }
-        Ok(value) => {
-            cstr_symbol = value;
-        }
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let symbol_handle = unsafe { _dlsym(handle._private, cstr_symbol.as_ptr()) };
-    Ok(symbol_handle)
-}
+    #[cfg(unix)]
+    const TEST_LIBRARY: &str = "libc.so.6";
+    #[cfg(unix)]
+    const TEST_SYMBOL: &str = "strlen";
 
-pub fn dlerror() -> Result<Option<String>, std::str::Utf8Error> {
-    unsafe {
-        let message = _dlerror();
-        if message.is_null() {
-            return Ok(None);
-        }
+    #[cfg(windows)]
+    const TEST_LIBRARY: &str = "kernel32.dll";
+    #[cfg(windows)]
+    const TEST_SYMBOL: &str = "GetProcAddress";
+
+    #[test]
+    fn sym_caches_on_repeat_lookup() {
+        let mut library = Library::open(TEST_LIBRARY, RTLD_NOW).expect("library should open");
+        assert_eq!(library.name(), TEST_LIBRARY);
+
+        let first = library.sym(TEST_SYMBOL).expect("symbol should resolve");
+        let second = library
+            .sym(TEST_SYMBOL)
+            .expect("cached symbol should resolve");
+        assert_eq!(first, second);
 
-        let message = std::ffi::CStr::from_ptr(message);
-        match message.to_str() {
-            Err(err) => Err(err),
-            Ok(message) => {
-                let message = message.to_owned();
-                Ok(Some(message))
-            }
+        unsafe {
+            assert_eq!(library.table().len(), 1);
         }
     }
 }
@@ -0,0 +1,9 @@
+//! Per-platform dynamic-loading backends. Each backend exposes the same
+//! `FileHandle`/`Symbol`/`dlopen`/`dlsym`/`dlclose`/`dlerror` surface so the
+//! crate root can re-export whichever one matches the target OS.
+
+#[cfg(unix)]
+pub mod unix;
+
+#[cfg(windows)]
+pub mod windows;
@@ -0,0 +1,429 @@
+use std::error::Error;
+use std::ffi::CString;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+use std::os::raw::{c_char, c_int, c_void};
+use std::sync::{Mutex, OnceLock};
+
+// #[link(name = "libc")]
+extern "system" {
+    #[link_name = "dlopen"]
+    fn _dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    #[link_name = "dlclose"]
+    fn _dlclose(handle: *mut c_void) -> c_int;
+    #[link_name = "dlsym"]
+    fn _dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    #[link_name = "dlerror"]
+    fn _dlerror() -> *mut c_char;
+    #[link_name = "dladdr"]
+    fn _dladdr(addr: *const c_void, info: *mut RawDlInfo) -> c_int;
+}
+
+// Mirrors glibc's `Dl_info` layout from <dlfcn.h>.
+#[repr(C)]
+struct RawDlInfo {
+    dli_fname: *const c_char,
+    dli_fbase: *mut c_void,
+    dli_sname: *const c_char,
+    dli_saddr: *mut c_void,
+}
+
+/// The error reported by `dlerror()` after a failed dlopen/dlsym/dlclose call.
+#[derive(Debug)]
+pub struct DlError(String);
+
+impl fmt::Display for DlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for DlError {}
+
+fn dlerror_mutex() -> &'static Mutex<()> {
+    static DLERROR_MUTEX: OnceLock<Mutex<()>> = OnceLock::new();
+    DLERROR_MUTEX.get_or_init(|| Mutex::new(()))
+}
+
+/// Runs `os_call` while holding the process-wide dlerror mutex, clearing any
+/// stale error beforehand and checking `dlerror()` again afterwards. glibc's
+/// error state is a single global buffer shared by every thread, and a
+/// successful `dlsym` can legitimately return NULL, so this is the only way
+/// to tell "call failed" from "call succeeded with a NULL result".
+fn with_dlerror<T>(os_call: impl FnOnce() -> T) -> Result<T, Box<dyn Error>> {
+    let _guard = dlerror_mutex().lock().unwrap();
+
+    unsafe {
+        _dlerror();
+    }
+
+    let result = os_call();
+
+    let error = unsafe { _dlerror() };
+    if !error.is_null() {
+        let message = unsafe { std::ffi::CStr::from_ptr(error) }
+            .to_string_lossy()
+            .into_owned();
+        return Err(Box::new(DlError(message)));
+    }
+
+    Ok(result)
+}
+
+/// FileHandle serves the purpose of not having direct access to the raw pointer
+/// handle returned by dlopen outside of this library.
+/// Every FileHandle obtained from dlopen corresponds to exactly one
+/// dlopen/dlclose calling pair.
+/// As such, there is no implementation of the Copy-Trait.
+/// Dropping a `FileHandle` closes it automatically (unless it is `invalid()`
+/// or a pseudo-handle), so callers no longer need to call `dlclose`
+/// themselves to avoid leaking it.
+pub struct FileHandle {
+    _private: *mut c_void,
+    // Pseudo-handles (`RTLD_DEFAULT`/`RTLD_NEXT`) don't come from `dlopen`
+    // and have no matching `dlclose`, so they must be exempt from the
+    // auto-close behavior below.
+    _auto_close: bool,
+}
+
+impl FileHandle {
+    /// Creates a handle that does not represent a file opened with dlopen.
+    /// Use case: replacing a FileHandle field in a struct.
+    pub fn invalid() -> Self {
+        FileHandle {
+            _private: std::ptr::null_mut(),
+            _auto_close: true,
+        }
+    }
+
+    /// The `RTLD_DEFAULT` pseudo-handle: resolves symbols against the
+    /// default global scope, in load order, without opening a file.
+    pub fn default_search() -> Self {
+        FileHandle {
+            _private: std::ptr::null_mut(),
+            _auto_close: false,
+        }
+    }
+
+    /// The `RTLD_NEXT` pseudo-handle: resolves symbols starting from the
+    /// library after the one making the call, in load order.
+    pub fn next() -> Self {
+        FileHandle {
+            _private: -1isize as *mut c_void,
+            _auto_close: false,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        // Pseudo-handles (RTLD_DEFAULT/RTLD_NEXT) are legitimate handles
+        // despite a null or sentinel `_private`, so `invalid()` can't be
+        // told apart from them by the pointer value alone.
+        !self._private.is_null() || !self._auto_close
+    }
+
+    /// Detaches the underlying dlopen handle from this wrapper, returning the
+    /// raw pointer without running `Drop`. The caller becomes responsible for
+    /// eventually passing it back to `from_raw` (or to `dlclose` via
+    /// `from_raw`) to avoid leaking the handle.
+    pub fn into_raw(self) -> *mut c_void {
+        let handle = self._private;
+        std::mem::forget(self);
+        handle
+    }
+
+    /// Reconstructs a `FileHandle` from a raw pointer previously obtained via
+    /// `into_raw`. The pointer must originate from a successful `dlopen` (or
+    /// be null, matching `invalid()`) and must not already be owned by
+    /// another `FileHandle`.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must either be null or a pointer previously returned by
+    /// `dlopen`/`into_raw` that is still open, and must not be owned by any
+    /// other `FileHandle` — otherwise this creates a second owner that will
+    /// call `dlclose` on it independently, leading to a double-close/use-
+    /// after-free once one of them drops.
+    pub unsafe fn from_raw(handle: *mut c_void) -> Self {
+        FileHandle {
+            _private: handle,
+            _auto_close: true,
+        }
+    }
+
+    /// Resolves `symbol` in this library and returns it as a typed, callable
+    /// `Symbol<T>` borrowed from this handle. `T` is typically an `unsafe
+    /// extern "C" fn(...) -> ...` pointer type; a `const` assertion rejects
+    /// any `T` that isn't pointer-sized, which would indicate an accidental
+    /// transmute to a fat pointer.
+    pub fn get<T>(&self, symbol: &str) -> Result<Symbol<'_, T>, Box<dyn Error>> {
+        AssertPointerSized::<T>::check();
+
+        let pointer = dlsym(self, symbol)?;
+        Ok(Symbol {
+            pointer,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl Drop for FileHandle {
+    fn drop(&mut self) {
+        if self._auto_close && !self._private.is_null() {
+            unsafe {
+                _dlclose(self._private);
+            }
+        }
+    }
+}
+
+/// A symbol resolved from a `FileHandle`, borrowed for the lifetime `'lib` of
+/// that handle so it cannot outlive (and be called after) the library it came
+/// from. `T` is normally a function pointer type; `Symbol<T>` derefs to `T`
+/// so the resolved symbol can be called directly.
+pub struct Symbol<'lib, T> {
+    pointer: *mut c_void,
+    _marker: PhantomData<&'lib T>,
+}
+
+impl<'lib, T> Deref for Symbol<'lib, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*(&self.pointer as *const *mut c_void as *const T) }
+    }
+}
+
+// Static assertion used by `FileHandle::get` to reject `T`s that aren't
+// pointer-sized (a fat pointer would silently read past `self.pointer`).
+struct AssertPointerSized<T>(PhantomData<T>);
+
+impl<T> AssertPointerSized<T> {
+    const CHECK: () = assert!(mem::size_of::<T>() == mem::size_of::<*mut c_void>());
+
+    fn check() {
+        Self::CHECK
+    }
+}
+
+// flags that can be passed to dlopen
+pub const RTLD_LAZY: i32 = 0x00001;
+pub const RTLD_NOW: i32 = 0x00002;
+pub const RTLD_BINDING_MASK: i32 = 0x00003;
+pub const RTLD_NOLOAD: i32 = 0x00004;
+pub const RTLD_DEEPBIND: i32 = 0x00008;
+pub const RTLD_GLOBAL: i32 = 0x00100;
+pub const RTLD_LOCAL: i32 = 0;
+pub const RTLD_NODELETE: i32 = 0x01000;
+
+pub fn dlopen(filename: &str, flags: i32) -> Result<FileHandle, Box<dyn Error>> {
+    if filename == "" {
+        let handle = with_dlerror(|| unsafe { _dlopen(std::ptr::null(), flags) })?;
+        return Ok(FileHandle {
+            _private: handle,
+            _auto_close: true,
+        });
+    } else {
+        let cstr_filename;
+        match CString::new(filename) {
+            Err(e) => return Err(Box::new(e)),
+            Ok(value) => {
+                cstr_filename = value;
+            }
+        }
+
+        let handle = with_dlerror(|| unsafe { _dlopen(cstr_filename.as_ptr(), flags) })?;
+        return Ok(FileHandle {
+            _private: handle,
+            _auto_close: true,
+        });
+    }
+}
+
+pub fn dlclose(handle: FileHandle) -> i32 {
+    // Pseudo-handles (RTLD_DEFAULT/RTLD_NEXT) were never dlopen'd, so there
+    // is nothing to close.
+    if !handle._auto_close {
+        return 0;
+    }
+    // Take the raw pointer and forget the wrapper so `Drop` doesn't also
+    // call `_dlclose` on it once this function returns.
+    let raw = handle.into_raw();
+    if raw.is_null() {
+        return 0;
+    }
+    with_dlerror(|| unsafe { _dlclose(raw) }).unwrap_or(-1)
+}
+
+pub fn dlsym(handle: &FileHandle, symbol: &str) -> Result<*mut c_void, Box<dyn Error>> {
+    let cstr_symbol;
+    match CString::new(symbol) {
+        Err(e) => {
+            return Err(Box::new(e));
+        }
+        Ok(value) => {
+            cstr_symbol = value;
+        }
+    }
+
+    // A successful dlsym can legitimately return NULL, so the real/absent
+    // error distinction has to come from dlerror(), not from the pointer.
+    with_dlerror(|| unsafe { _dlsym(handle._private, cstr_symbol.as_ptr()) })
+}
+
+/// Information about the shared object and symbol an address belongs to, as
+/// returned by `dladdr`.
+pub struct DlInfo {
+    /// Path of the shared object containing `addr`.
+    pub dli_fname: String,
+    /// Base address at which the shared object is loaded.
+    pub dli_fbase: *mut c_void,
+    /// Name of the nearest symbol with an address lower than or equal to
+    /// `addr`, if any symbol table info is available.
+    pub dli_sname: Option<String>,
+    /// Exact address of that symbol.
+    pub dli_saddr: *mut c_void,
+}
+
+/// Resolves `addr` to the shared object and (if available) symbol it
+/// belongs to. Returns `None` if `addr` could not be matched to any loaded
+/// object, mirroring `dladdr` returning 0.
+// `addr` is only ever used as an opaque lookup key passed through to the
+// underlying `dladdr` call; it is never dereferenced here.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn dladdr(addr: *const c_void) -> Option<DlInfo> {
+    let mut info = std::mem::MaybeUninit::<RawDlInfo>::uninit();
+    let result = unsafe { _dladdr(addr, info.as_mut_ptr()) };
+    if result == 0 {
+        return None;
+    }
+
+    let info = unsafe { info.assume_init() };
+    let dli_fname = if info.dli_fname.is_null() {
+        String::new()
+    } else {
+        unsafe { std::ffi::CStr::from_ptr(info.dli_fname) }
+            .to_string_lossy()
+            .into_owned()
+    };
+    let dli_sname = if info.dli_sname.is_null() {
+        None
+    } else {
+        Some(
+            unsafe { std::ffi::CStr::from_ptr(info.dli_sname) }
+                .to_string_lossy()
+                .into_owned(),
+        )
+    };
+
+    Some(DlInfo {
+        dli_fname,
+        dli_fbase: info.dli_fbase,
+        dli_sname,
+        dli_saddr: info.dli_saddr,
+    })
+}
+
+pub fn dlerror() -> Result<Option<String>, std::str::Utf8Error> {
+    let _guard = dlerror_mutex().lock().unwrap();
+    unsafe {
+        let message = _dlerror();
+        if message.is_null() {
+            return Ok(None);
+        }
+
+        let message = std::ffi::CStr::from_ptr(message);
+        match message.to_str() {
+            Err(err) => Err(err),
+            Ok(message) => {
+                let message = message.to_owned();
+                Ok(Some(message))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pseudo_handles_are_valid_but_invalid_is_not() {
+        assert!(FileHandle::default_search().is_valid());
+        assert!(FileHandle::next().is_valid());
+        assert!(!FileHandle::invalid().is_valid());
+    }
+
+    #[test]
+    fn dlclose_is_a_noop_for_pseudo_handles() {
+        assert_eq!(dlclose(FileHandle::default_search()), 0);
+        assert_eq!(dlclose(FileHandle::next()), 0);
+    }
+
+    #[test]
+    fn get_resolves_a_callable_typed_symbol() {
+        let handle = FileHandle::default_search();
+        let strlen: Symbol<unsafe extern "C" fn(*const c_char) -> usize> =
+            handle.get("strlen").expect("strlen should resolve");
+        let text = CString::new("hello").unwrap();
+        let len = unsafe { strlen(text.as_ptr()) };
+        assert_eq!(len, 5);
+    }
+
+    #[test]
+    fn into_raw_from_raw_roundtrip_closes_exactly_once() {
+        let handle = dlopen("libc.so.6", RTLD_NOW).expect("libc.so.6 should be dlopen-able");
+        let raw = handle.into_raw();
+        assert!(!raw.is_null());
+        // Reconstructing from the raw pointer and letting it drop here must
+        // close the handle exactly once. `Drop` ignores the result of
+        // `dlclose`, so this only checks that the round trip yields a
+        // non-null pointer and that dropping it does not crash.
+        let handle = unsafe { FileHandle::from_raw(raw) };
+        drop(handle);
+    }
+
+    // `strlen` is always present in the process (libc is always linked),
+    // so resolving it against RTLD_DEFAULT exercises the real dlsym success
+    // path without needing to dlopen anything ourselves.
+    #[test]
+    fn dlsym_resolves_a_real_symbol() {
+        let handle = FileHandle::default_search();
+        let pointer = dlsym(&handle, "strlen").expect("strlen should resolve");
+        assert!(!pointer.is_null());
+    }
+
+    #[test]
+    fn dlsym_reports_an_error_for_an_unknown_symbol() {
+        let handle = FileHandle::default_search();
+        let err = dlsym(&handle, "this_symbol_does_not_exist_anywhere_1234")
+            .expect_err("bogus symbol should not resolve");
+        assert!(!err.to_string().is_empty());
+    }
+
+    // Hammers with_dlerror's clear/check protocol from multiple threads at
+    // once, mixing successful and failing lookups, to catch races on the
+    // shared dlerror mutex/buffer.
+    #[test]
+    fn dlsym_is_thread_safe() {
+        use std::thread;
+
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                thread::spawn(move || {
+                    let handle = FileHandle::default_search();
+                    if i % 2 == 0 {
+                        dlsym(&handle, "strlen").is_ok()
+                    } else {
+                        dlsym(&handle, "this_symbol_does_not_exist_anywhere_1234").is_err()
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            assert!(thread.join().unwrap());
+        }
+    }
+}
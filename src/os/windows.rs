@@ -0,0 +1,389 @@
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+use std::os::raw::{c_char, c_void};
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::sync::{Mutex, OnceLock};
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn LoadLibraryW(lp_lib_file_name: *const u16) -> *mut c_void;
+    fn GetModuleHandleW(lp_module_name: *const u16) -> *mut c_void;
+    fn FreeLibrary(h_lib_module: *mut c_void) -> i32;
+    fn GetProcAddress(h_module: *mut c_void, lp_proc_name: *const c_char) -> *mut c_void;
+    fn GetLastError() -> u32;
+    fn SetLastError(dw_err_code: u32);
+    fn FormatMessageW(
+        dw_flags: u32,
+        lp_source: *const c_void,
+        dw_message_id: u32,
+        dw_language_id: u32,
+        lp_buffer: *mut u16,
+        n_size: u32,
+        arguments: *mut c_void,
+    ) -> u32;
+}
+
+const FORMAT_MESSAGE_FROM_SYSTEM: u32 = 0x0000_1000;
+const FORMAT_MESSAGE_IGNORE_INSERTS: u32 = 0x0000_0200;
+
+/// The error reported by `GetLastError`/`FormatMessageW` after a failed
+/// dlopen/dlsym/dlclose call.
+#[derive(Debug)]
+pub struct DlError(String);
+
+impl fmt::Display for DlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for DlError {}
+
+fn dlerror_mutex() -> &'static Mutex<()> {
+    static DLERROR_MUTEX: OnceLock<Mutex<()>> = OnceLock::new();
+    DLERROR_MUTEX.get_or_init(|| Mutex::new(()))
+}
+
+fn to_wide(value: &str) -> Vec<u16> {
+    OsStr::new(value)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect()
+}
+
+fn format_last_error(code: u32) -> String {
+    let mut buffer = [0u16; 512];
+    let len = unsafe {
+        FormatMessageW(
+            FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS,
+            std::ptr::null(),
+            code,
+            0,
+            buffer.as_mut_ptr(),
+            buffer.len() as u32,
+            std::ptr::null_mut(),
+        )
+    };
+
+    if len == 0 {
+        return format!("unknown error {code:#x}");
+    }
+
+    OsString::from_wide(&buffer[..len as usize])
+        .to_string_lossy()
+        .trim_end()
+        .to_owned()
+}
+
+/// Runs `os_call`, clearing any stale last-error code beforehand and
+/// checking `GetLastError()` again afterwards, mirroring the glibc
+/// `dlerror()` clear-then-check protocol so the two backends behave the
+/// same way from the caller's perspective. `GetLastError` is thread-local
+/// and `format_last_error` formats into a stack buffer, so nothing here is
+/// shared between threads; the mutex only serializes calls the same way the
+/// glibc backend does.
+fn with_dlerror<T>(os_call: impl FnOnce() -> T) -> Result<T, Box<dyn Error>> {
+    let _guard = dlerror_mutex().lock().unwrap();
+
+    unsafe {
+        SetLastError(0);
+    }
+
+    let result = os_call();
+
+    let code = unsafe { GetLastError() };
+    if code != 0 {
+        return Err(Box::new(DlError(format_last_error(code))));
+    }
+
+    Ok(result)
+}
+
+/// FileHandle serves the purpose of not having direct access to the raw pointer
+/// (HMODULE) returned by LoadLibraryW outside of this library.
+/// Every FileHandle obtained from LoadLibraryW corresponds to exactly one
+/// LoadLibraryW/FreeLibrary calling pair.
+/// As such, there is no implementation of the Copy-Trait.
+/// Dropping a `FileHandle` closes it automatically (unless it is `invalid()`
+/// or was obtained through `GetModuleHandleW`), so callers no longer need to
+/// call `dlclose` themselves to avoid leaking it.
+pub struct FileHandle {
+    _private: *mut c_void,
+    // `GetModuleHandleW` (used for the empty-filename "main module" lookup
+    // and for `RTLD_NOLOAD`) does not take a new reference on the module,
+    // unlike `LoadLibraryW`. Calling `FreeLibrary` on a handle we never
+    // incremented the refcount of would unload a DLL other code in the
+    // process may still be using, so such handles must be exempt from the
+    // auto-close behavior below.
+    _owns_reference: bool,
+}
+
+impl FileHandle {
+    /// Creates a handle that does not represent a library loaded with
+    /// LoadLibraryW.
+    /// Use case: replacing a FileHandle field in a struct.
+    pub fn invalid() -> Self {
+        FileHandle {
+            _private: std::ptr::null_mut(),
+            _owns_reference: true,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        !self._private.is_null()
+    }
+
+    /// Detaches the underlying HMODULE from this wrapper, returning the raw
+    /// pointer without running `Drop`. The caller becomes responsible for
+    /// eventually passing it back to `from_raw` to avoid leaking the handle.
+    pub fn into_raw(self) -> *mut c_void {
+        let handle = self._private;
+        std::mem::forget(self);
+        handle
+    }
+
+    /// Reconstructs a `FileHandle` from a raw pointer previously obtained via
+    /// `into_raw`. The pointer must originate from a successful load (or be
+    /// null, matching `invalid()`) and must not already be owned by another
+    /// `FileHandle`.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must either be null or a valid HMODULE that this process
+    /// holds a reference on (e.g. previously obtained from `into_raw`), and
+    /// must not be owned by any other `FileHandle` — otherwise this creates
+    /// a second owner that will call `FreeLibrary` on it independently,
+    /// leading to a use-after-free once one of them drops.
+    pub unsafe fn from_raw(handle: *mut c_void) -> Self {
+        FileHandle {
+            _private: handle,
+            _owns_reference: true,
+        }
+    }
+
+    /// Resolves `symbol` in this library and returns it as a typed, callable
+    /// `Symbol<T>` borrowed from this handle. `T` is typically an `unsafe
+    /// extern "system" fn(...) -> ...` pointer type; a `const` assertion
+    /// rejects any `T` that isn't pointer-sized, which would indicate an
+    /// accidental transmute to a fat pointer.
+    pub fn get<T>(&self, symbol: &str) -> Result<Symbol<'_, T>, Box<dyn Error>> {
+        AssertPointerSized::<T>::check();
+
+        let pointer = dlsym(self, symbol)?;
+        Ok(Symbol {
+            pointer,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl Drop for FileHandle {
+    fn drop(&mut self) {
+        if self._owns_reference && !self._private.is_null() {
+            unsafe {
+                FreeLibrary(self._private);
+            }
+        }
+    }
+}
+
+/// A symbol resolved from a `FileHandle`, borrowed for the lifetime `'lib` of
+/// that handle so it cannot outlive (and be called after) the library it came
+/// from. `T` is normally a function pointer type; `Symbol<T>` derefs to `T`
+/// so the resolved symbol can be called directly.
+pub struct Symbol<'lib, T> {
+    pointer: *mut c_void,
+    _marker: PhantomData<&'lib T>,
+}
+
+impl<'lib, T> Deref for Symbol<'lib, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*(&self.pointer as *const *mut c_void as *const T) }
+    }
+}
+
+// Static assertion used by `FileHandle::get` to reject `T`s that aren't
+// pointer-sized (a fat pointer would silently read past `self.pointer`).
+struct AssertPointerSized<T>(PhantomData<T>);
+
+impl<T> AssertPointerSized<T> {
+    const CHECK: () = assert!(mem::size_of::<T>() == mem::size_of::<*mut c_void>());
+
+    fn check() {
+        Self::CHECK
+    }
+}
+
+// Flags accepted by `dlopen`. These mirror the glibc `RTLD_*` values so the
+// same call sites compile for both backends; most have no Windows
+// equivalent and are ignored by this backend (Windows always resolves
+// imports eagerly and modules are process-global), except `RTLD_NOLOAD`,
+// which is mapped to `GetModuleHandleW`.
+pub const RTLD_LAZY: i32 = 0x00001;
+pub const RTLD_NOW: i32 = 0x00002;
+pub const RTLD_BINDING_MASK: i32 = 0x00003;
+pub const RTLD_NOLOAD: i32 = 0x00004;
+pub const RTLD_DEEPBIND: i32 = 0x00008;
+pub const RTLD_GLOBAL: i32 = 0x00100;
+pub const RTLD_LOCAL: i32 = 0;
+pub const RTLD_NODELETE: i32 = 0x01000;
+
+pub fn dlopen(filename: &str, flags: i32) -> Result<FileHandle, Box<dyn Error>> {
+    if filename == "" {
+        let handle = with_dlerror(|| unsafe { GetModuleHandleW(std::ptr::null()) })?;
+        return Ok(FileHandle {
+            _private: handle,
+            _owns_reference: false,
+        });
+    }
+
+    let wide_filename = to_wide(filename);
+
+    if flags & RTLD_NOLOAD != 0 {
+        let handle = with_dlerror(|| unsafe { GetModuleHandleW(wide_filename.as_ptr()) })?;
+        return Ok(FileHandle {
+            _private: handle,
+            _owns_reference: false,
+        });
+    }
+
+    let handle = with_dlerror(|| unsafe { LoadLibraryW(wide_filename.as_ptr()) })?;
+    Ok(FileHandle {
+        _private: handle,
+        _owns_reference: true,
+    })
+}
+
+pub fn dlclose(handle: FileHandle) -> i32 {
+    // GetModuleHandleW-sourced handles never took a reference, so there is
+    // nothing for us to release.
+    if !handle._owns_reference {
+        return 0;
+    }
+    // Take the raw pointer and forget the wrapper so `Drop` doesn't also
+    // call `FreeLibrary` on it once this function returns.
+    let raw = handle.into_raw();
+    if raw.is_null() {
+        return 0;
+    }
+    match with_dlerror(|| unsafe { FreeLibrary(raw) }) {
+        Ok(result) if result != 0 => 0,
+        _ => -1,
+    }
+}
+
+pub fn dlsym(handle: &FileHandle, symbol: &str) -> Result<*mut c_void, Box<dyn Error>> {
+    let cstr_symbol;
+    match std::ffi::CString::new(symbol) {
+        Err(e) => {
+            return Err(Box::new(e));
+        }
+        Ok(value) => {
+            cstr_symbol = value;
+        }
+    }
+
+    // A successful GetProcAddress can legitimately return NULL (an ordinal
+    // import resolving to address zero never happens in practice, but
+    // nothing guarantees otherwise), so the real/absent error distinction
+    // has to come from GetLastError(), not from the pointer.
+    with_dlerror(|| unsafe { GetProcAddress(handle._private, cstr_symbol.as_ptr()) })
+}
+
+pub fn dlerror() -> Result<Option<String>, std::str::Utf8Error> {
+    let _guard = dlerror_mutex().lock().unwrap();
+    let code = unsafe { GetLastError() };
+    if code == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(format_last_error(code)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RTLD_NOLOAD routes through GetModuleHandleW, which does not take a new
+    // reference on the module. Dropping (or dlclose-ing) a handle acquired
+    // this way must not FreeLibrary kernel32.dll out from under the rest of
+    // the process.
+    #[test]
+    fn noload_handle_is_not_freed_on_drop() {
+        let handle = dlopen("kernel32.dll", RTLD_NOLOAD).expect("kernel32.dll is already loaded");
+        assert!(handle.is_valid());
+        drop(handle);
+
+        let handle =
+            dlopen("kernel32.dll", RTLD_NOLOAD).expect("kernel32.dll should still be loaded");
+        let pointer =
+            dlsym(&handle, "GetProcAddress").expect("GetProcAddress should still resolve");
+        assert!(!pointer.is_null());
+    }
+
+    #[test]
+    fn dlclose_is_a_noop_for_noload_handle() {
+        let handle = dlopen("kernel32.dll", RTLD_NOLOAD).expect("kernel32.dll is already loaded");
+        assert_eq!(dlclose(handle), 0);
+    }
+
+    #[test]
+    fn get_resolves_a_callable_typed_symbol() {
+        let handle = dlopen("kernel32.dll", RTLD_NOLOAD).expect("kernel32.dll is already loaded");
+        let get_last_error: Symbol<unsafe extern "system" fn() -> u32> = handle
+            .get("GetLastError")
+            .expect("GetLastError should resolve");
+        unsafe {
+            get_last_error();
+        }
+    }
+
+    // kernel32.dll is always loaded in-process, so this exercises the real
+    // GetProcAddress success path without needing to load anything ourselves.
+    #[test]
+    fn dlsym_resolves_a_real_symbol() {
+        let handle = dlopen("kernel32.dll", RTLD_NOLOAD).expect("kernel32.dll is already loaded");
+        let pointer = dlsym(&handle, "GetProcAddress").expect("GetProcAddress should resolve");
+        assert!(!pointer.is_null());
+    }
+
+    #[test]
+    fn dlsym_reports_an_error_for_an_unknown_symbol() {
+        let handle = dlopen("kernel32.dll", RTLD_NOLOAD).expect("kernel32.dll is already loaded");
+        let err = dlsym(&handle, "this_symbol_does_not_exist_anywhere_1234")
+            .expect_err("bogus symbol should not resolve");
+        assert!(!err.to_string().is_empty());
+    }
+
+    // Hammers with_dlerror's clear/check protocol from multiple threads at
+    // once, mixing successful and failing lookups, to check that each thread
+    // sees its own success or error result.
+    #[test]
+    fn dlsym_is_thread_safe() {
+        use std::thread;
+
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                thread::spawn(move || {
+                    let handle = dlopen("kernel32.dll", RTLD_NOLOAD)
+                        .expect("kernel32.dll is already loaded");
+                    if i % 2 == 0 {
+                        dlsym(&handle, "GetProcAddress").is_ok()
+                    } else {
+                        dlsym(&handle, "this_symbol_does_not_exist_anywhere_1234").is_err()
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            assert!(thread.join().unwrap());
+        }
+    }
+}